@@ -0,0 +1,187 @@
+//! Named devices and scenes loaded from a YAML config, applied to every device at once.
+//!
+//! A scene is applied by connecting to every device it names up front, then releasing
+//! all of the perform-action calls across a `Barrier` so the strips change in lockstep.
+
+use serde::Deserialize;
+
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+use led::magic_home::{Actions, LedError, MagicHomeAPI};
+
+#[derive(Debug, Deserialize)]
+pub struct DeviceConfig {
+    pub address: String,
+    pub port: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    pub devices: HashMap<String, DeviceConfig>,
+    pub scenes: HashMap<String, HashMap<String, String>>,
+}
+
+impl Config {
+    /// Loads and parses a scenes config from a YAML file
+    pub fn from_path(path: &str) -> Result<Config, LedError> {
+        let contents =
+            fs::read_to_string(path).map_err(|err| LedError::Config(err.to_string()))?;
+
+        serde_yaml::from_str(&contents).map_err(|err| LedError::Config(err.to_string()))
+    }
+}
+
+/// Per-device result of applying a scene
+type DeviceResult = (String, Result<(), LedError>);
+
+/// Applies `scene` to every device it names, changing them all at (as close to) the same instant.
+/// Returns one result per named device, so a failure on one device doesn't hide the others.
+pub fn apply_scene(config: &Config, scene: &str) -> Result<Vec<DeviceResult>, LedError> {
+    let devices = config
+        .scenes
+        .get(scene)
+        .ok_or_else(|| LedError::UnknownScene(scene.to_string()))?;
+
+    // Connect to every device up front: a connection failure shouldn't leave the
+    // other threads waiting forever on the barrier.
+    let mut connected = Vec::new();
+    let mut results = Vec::new();
+
+    for (device_name, action_str) in devices {
+        match connect(config, device_name) {
+            Ok(api) => connected.push((device_name.clone(), action_str.clone(), api)),
+            Err(err) => results.push((device_name.clone(), Err(err))),
+        }
+    }
+
+    let barrier = Arc::new(Barrier::new(connected.len()));
+
+    let applied = thread::scope(|scope| {
+        let handles: Vec<_> = connected
+            .into_iter()
+            .map(|(device_name, action_str, mut api)| {
+                let barrier = Arc::clone(&barrier);
+
+                scope.spawn(move || {
+                    let action = action_from_str(&action_str);
+
+                    barrier.wait();
+
+                    let result = api.perform_action(&action).map(|_| ());
+
+                    (device_name, result)
+                })
+            })
+            .collect();
+
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    results.extend::<Vec<_>>(applied);
+
+    Ok(results)
+}
+
+fn connect(config: &Config, device_name: &str) -> Result<MagicHomeAPI, LedError> {
+    let device = config
+        .devices
+        .get(device_name)
+        .ok_or_else(|| LedError::UnknownDevice(device_name.to_string()))?;
+
+    MagicHomeAPI::new(&device.address, device.port.as_deref())
+}
+
+/// Maps a scene value onto an `Actions`: a known preset name, or otherwise a color
+/// handed straight to `Actions::Set`
+fn action_from_str(value: &str) -> Actions {
+    match value {
+        "status" => Actions::Status,
+        "on" => Actions::On,
+        "off" => Actions::Off,
+        "chaos" => Actions::Chaos { speed: None },
+        "rainbow" => Actions::Rainbow { speed: None },
+        "ambient" => Actions::Ambient { speed: None },
+        "red" => Actions::Red,
+        "green" => Actions::Green,
+        "blue" => Actions::Blue,
+        "yellow" => Actions::Yellow,
+        "orange" => Actions::Orange,
+        "lime" => Actions::Lime,
+        "purple" => Actions::Purple,
+        "pink" => Actions::Pink,
+        "cyan" => Actions::Cyan,
+        "white" => Actions::White,
+        color => Actions::Set {
+            color: color.to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn config_with_scene(port: u16) -> Config {
+        let mut devices = HashMap::new();
+        devices.insert(
+            "living_room".to_string(),
+            DeviceConfig {
+                address: "127.0.0.1".to_string(),
+                port: Some(port.to_string()),
+            },
+        );
+
+        let mut scene = HashMap::new();
+        scene.insert("living_room".to_string(), "on".to_string());
+
+        let mut scenes = HashMap::new();
+        scenes.insert("party".to_string(), scene);
+
+        Config { devices, scenes }
+    }
+
+    #[test]
+    fn unknown_scene_is_an_error() {
+        let config = config_with_scene(9986);
+        let result = apply_scene(&config, "bogus");
+        assert!(matches!(result, Err(LedError::UnknownScene(_))));
+    }
+
+    #[test]
+    fn known_scene_applies_to_its_device() {
+        let _listener = TcpListener::bind(("127.0.0.1", 9987)).unwrap();
+        let config = config_with_scene(9987);
+
+        let results = apply_scene(&config, "party").unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, "living_room");
+        assert!(results[0].1.is_ok());
+    }
+
+    #[test]
+    fn scene_naming_unknown_device_reports_a_per_device_error() {
+        let mut config = config_with_scene(9988);
+        config
+            .scenes
+            .get_mut("party")
+            .unwrap()
+            .insert("ghost".to_string(), "on".to_string());
+        // Drop the device this scene actually needs too, so neither name is a known device
+        config.devices.remove("living_room");
+
+        let results = apply_scene(&config, "party").unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert!(results
+            .iter()
+            .any(|(name, result)| name == "ghost" && matches!(result, Err(LedError::UnknownDevice(_)))));
+        assert!(results
+            .iter()
+            .any(|(name, result)| name == "living_room" && matches!(result, Err(LedError::UnknownDevice(_)))));
+    }
+}
@@ -1,8 +1,10 @@
 use clap::Subcommand;
+use thiserror::Error;
 
-use std::error::Error;
 use std::fmt;
+use std::io;
 use std::io::prelude::*;
+use std::io::IsTerminal;
 use std::net::TcpStream;
 
 type Control = (u8, u8, u8);
@@ -51,13 +53,25 @@ pub enum Actions {
     Off,
 
     /// Red strobe
-    Chaos,
+    Chaos {
+        /// Speed from 0 (slowest) to 100 (fastest), overriding the preset default
+        #[clap(long)]
+        speed: Option<u8>,
+    },
 
     /// Fast cycle
-    Rainbow,
+    Rainbow {
+        /// Speed from 0 (slowest) to 100 (fastest), overriding the preset default
+        #[clap(long)]
+        speed: Option<u8>,
+    },
 
     /// Slow cycle
-    Ambient,
+    Ambient {
+        /// Speed from 0 (slowest) to 100 (fastest), overriding the preset default
+        #[clap(long)]
+        speed: Option<u8>,
+    },
 
     /// Red static
     Red,
@@ -88,6 +102,135 @@ pub enum Actions {
 
     /// White static
     White,
+
+    /// Custom static color, given as `#rrggbb`/`rrggbb` hex, an `r,g,b` decimal
+    /// triple, or a named color (e.g. "red")
+    Set {
+        color: String,
+    },
+
+    /// Run an HTTP server that forwards requests to the device
+    Serve {
+        /// Address to listen on
+        #[clap(short, long, default_value = "0.0.0.0:8080")]
+        listen: String,
+    },
+}
+
+/// Errors that can occur while talking to a device or parsing user input
+#[derive(Error, Debug)]
+pub enum LedError {
+    #[error("failed to connect to device")]
+    Connect(#[source] io::Error),
+
+    #[error("i/o error communicating with device")]
+    Io(#[from] io::Error),
+
+    #[error("invalid {channel} value: {value} (expected 0-255)")]
+    InvalidColor { channel: &'static str, value: isize },
+
+    #[error("short status read: got {got} bytes, expected {expected}")]
+    ShortStatusRead { got: usize, expected: usize },
+
+    #[error("invalid hex color '{0}'")]
+    InvalidHex(String),
+
+    #[error("invalid rgb triple '{0}'")]
+    InvalidTriple(String),
+
+    #[error("unknown color name '{0}'")]
+    UnknownColor(String),
+
+    #[error("failed to load config: {0}")]
+    Config(String),
+
+    #[error("unknown scene '{0}'")]
+    UnknownScene(String),
+
+    #[error("unknown device '{0}' referenced in scene")]
+    UnknownDevice(String),
+
+    #[error("'{0}' is not a wire-protocol action and cannot be dispatched directly")]
+    NotDispatchable(&'static str),
+}
+
+/// A color known by name, looked up the same way the preset `Rgb` constants are defined
+struct NamedColor(Rgb);
+
+impl TryFrom<&str> for NamedColor {
+    type Error = LedError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let rgb = match value {
+            "red" => RED,
+            "green" => GREEN,
+            "blue" => BLUE,
+            "lime" => LIME,
+            "yellow" => YELLOW,
+            "pink" => PINK,
+            "cyan" => CYAN,
+            "purple" => PURPLE,
+            "orange" => ORANGE,
+            "white" => WHITE,
+            _ => return Err(LedError::UnknownColor(value.to_string())),
+        };
+
+        Ok(NamedColor(rgb))
+    }
+}
+
+/// Parses a color given as `#rrggbb`/`rrggbb` hex, an `r,g,b` decimal triple, or a named color
+fn parse_color(color: &str) -> Result<Rgb, LedError> {
+    if let Some(hex) = color.strip_prefix('#') {
+        return parse_hex(hex, color);
+    }
+
+    if color.contains(',') {
+        return parse_triple(color);
+    }
+
+    if color.len() == 6 && color.chars().all(|c| c.is_ascii_hexdigit()) {
+        return parse_hex(color, color);
+    }
+
+    NamedColor::try_from(color).map(|c| c.0)
+}
+
+/// Parses a bare 6-digit hex string, reporting the original (possibly `#`-prefixed) input on error
+fn parse_hex(hex: &str, original: &str) -> Result<Rgb, LedError> {
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(LedError::InvalidHex(original.to_string()));
+    }
+
+    let r = u8::from_str_radix(&hex[0..2], 16).unwrap();
+    let g = u8::from_str_radix(&hex[2..4], 16).unwrap();
+    let b = u8::from_str_radix(&hex[4..6], 16).unwrap();
+
+    Ok((r, g, b))
+}
+
+/// Parses a `r,g,b` decimal triple
+fn parse_triple(color: &str) -> Result<Rgb, LedError> {
+    let parts: Vec<&str> = color.split(',').collect();
+    if parts.len() != 3 {
+        return Err(LedError::InvalidTriple(color.to_string()));
+    }
+
+    let channels: Result<Vec<u8>, _> = parts.iter().map(|p| p.trim().parse::<u8>()).collect();
+
+    match channels {
+        Ok(channels) => Ok((channels[0], channels[1], channels[2])),
+        Err(_) => Err(LedError::InvalidTriple(color.to_string())),
+    }
+}
+
+/// Overrides a preset function's speed byte, clamping to 0..=100 and inverting it the
+/// same way `Status::from` parses it back (`100 - speed`), so the value round-trips
+fn with_speed((preset, default_speed): Function, speed: Option<u8>) -> Function {
+    match speed {
+        Some(speed) => (preset, 100 - speed.min(100)),
+        None => (preset, default_speed),
+    }
 }
 
 /// Object representing the status of the LED
@@ -127,9 +270,15 @@ impl From<&[u8; 14]> for Status {
     }
 }
 
-impl fmt::Display for Status {
-    /// Formats status object into readable text
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl Status {
+    /// Formats status as plain text, with no ANSI color escapes regardless of
+    /// whether stdout is a terminal. Used by callers that don't print straight
+    /// to the user's terminal, like the HTTP server.
+    pub fn to_plain_string(&self) -> String {
+        self.format(false)
+    }
+
+    fn format(&self, colorize: bool) -> String {
         let mut string = String::new();
 
         if self.power {
@@ -137,13 +286,31 @@ impl fmt::Display for Status {
         } else {
             string.push_str("Power: off\n");
         }
-        string.push_str(&format!("Color: {:?}\n", self.color));
+
+        if colorize {
+            let (r, g, b) = self.color;
+            string.push_str(&format!(
+                "Color: \x1b[48;2;{r};{g};{b}m  \x1b[0m {:?}\n",
+                self.color
+            ));
+        } else {
+            string.push_str(&format!("Color: {:?}\n", self.color));
+        }
+
         string.push_str(&format!("Mode: {}", self.mode));
         if let Some(speed) = self.speed {
             string.push_str(&format!("\nSpeed: {}", speed));
         }
 
-        write!(f, "{}", string)
+        string
+    }
+}
+
+impl fmt::Display for Status {
+    /// Formats status object into readable text, with a true-color swatch when
+    /// stdout is a terminal
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format(io::stdout().is_terminal()))
     }
 }
 
@@ -152,40 +319,45 @@ pub struct MagicHomeAPI(TcpStream);
 impl MagicHomeAPI {
     /// Creates api from device address
     /// If no port is provided defaults to 5577
-    pub fn new(address: &str, port: Option<&str>) -> Result<MagicHomeAPI, Box<dyn Error>> {
+    pub fn new(address: &str, port: Option<&str>) -> Result<MagicHomeAPI, LedError> {
         let port = port.unwrap_or(DEFAULT_PORT);
         let address = &format!("{}:{}", address, port);
-        let stream = TcpStream::connect(address)?;
+        let stream = TcpStream::connect(address).map_err(LedError::Connect)?;
 
         Ok(MagicHomeAPI(stream))
     }
 
-    /// Sets color of device according to Rgb values
-    #[allow(dead_code, unused_must_use)]
-    pub fn set_rgb(&mut self, r: isize, g: isize, b: isize) -> Result<(), &'static str> {
+    /// Sets color of device according to Rgb values, after checking each channel is in 0..=255
+    pub fn set_rgb(&mut self, r: isize, g: isize, b: isize) -> Result<Option<Status>, LedError> {
         if !(0..=255).contains(&r) {
-            Err("Invalid r value")
+            Err(LedError::InvalidColor {
+                channel: "r",
+                value: r,
+            })
         } else if !(0..=255).contains(&g) {
-            Err("Invalid g value")
+            Err(LedError::InvalidColor {
+                channel: "g",
+                value: g,
+            })
         } else if !(0..=255).contains(&b) {
-            Err("Invalid b value")
+            Err(LedError::InvalidColor {
+                channel: "b",
+                value: b,
+            })
         } else {
-            let message = Message::Color((r as u8, g as u8, b as u8));
-            self.send_to_device(message);
-
-            Ok(())
+            self.send_to_device(Message::Color((r as u8, g as u8, b as u8)))
         }
     }
 
     /// Changes mode of device to one of the preset functions or colors or gets status of device
-    pub fn perform_action(&mut self, action: &Actions) -> Result<Option<Status>, Box<dyn Error>> {
+    pub fn perform_action(&mut self, action: &Actions) -> Result<Option<Status>, LedError> {
         let message = match action {
             Actions::Status => Message::Control(STATUS),
             Actions::On => Message::Control(ON),
             Actions::Off => Message::Control(OFF),
-            Actions::Chaos => Message::Function(CHAOS),
-            Actions::Ambient => Message::Function(AMBIENT),
-            Actions::Rainbow => Message::Function(RAINBOW),
+            Actions::Chaos { speed } => Message::Function(with_speed(CHAOS, *speed)),
+            Actions::Ambient { speed } => Message::Function(with_speed(AMBIENT, *speed)),
+            Actions::Rainbow { speed } => Message::Function(with_speed(RAINBOW, *speed)),
             Actions::Red => Message::Color(RED),
             Actions::Green => Message::Color(GREEN),
             Actions::Blue => Message::Color(BLUE),
@@ -196,12 +368,17 @@ impl MagicHomeAPI {
             Actions::Purple => Message::Color(PURPLE),
             Actions::Orange => Message::Color(ORANGE),
             Actions::White => Message::Color(WHITE),
+            Actions::Set { color } => {
+                let (r, g, b) = parse_color(color)?;
+                return self.set_rgb(r as isize, g as isize, b as isize);
+            }
+            Actions::Serve { .. } => return Err(LedError::NotDispatchable("serve")),
         };
 
         self.send_to_device(message)
     }
 
-    fn send_to_device(&mut self, message: Message) -> Result<Option<Status>, Box<dyn Error>> {
+    fn send_to_device(&mut self, message: Message) -> Result<Option<Status>, LedError> {
         let mut bytes = match message {
             Message::Color((r, g, b)) => vec![0x31, r, b, g, 0xff, 0x00, 0x0f],
             Message::Function((preset, speed)) => {
@@ -222,7 +399,24 @@ impl MagicHomeAPI {
         if let Message::Control(STATUS) = message {
             let mut buffer: [u8; STATUS_BUFFER_SIZE] = [0; STATUS_BUFFER_SIZE];
 
-            self.0.read_exact(&mut buffer)?;
+            // A single read() can return less than the full status if it arrives split
+            // across TCP segments, so keep reading until the buffer is full or the
+            // connection closes early.
+            let mut got = 0;
+            while got < STATUS_BUFFER_SIZE {
+                let n = self.0.read(&mut buffer[got..])?;
+                if n == 0 {
+                    break;
+                }
+                got += n;
+            }
+
+            if got != STATUS_BUFFER_SIZE {
+                return Err(LedError::ShortStatusRead {
+                    got,
+                    expected: STATUS_BUFFER_SIZE,
+                });
+            }
 
             Ok(Some(Status::from(&buffer)))
         } else {
@@ -271,7 +465,7 @@ mod tests {
         let _a = TcpListener::bind("127.0.0.1:9997").unwrap();
         let mut api = MagicHomeAPI::new("127.0.0.1", Some("9997")).unwrap();
         let result = api.set_rgb(255, 1, 0);
-        assert_eq!(result, Ok(()));
+        assert!(result.is_ok());
     }
 
     #[test]
@@ -279,7 +473,13 @@ mod tests {
         let _a = TcpListener::bind("127.0.0.1:9996").unwrap();
         let mut api = MagicHomeAPI::new("127.0.0.1", Some("9996")).unwrap();
         let result = api.set_rgb(255, -1, 0);
-        assert_eq!(result, Err("Invalid g value"));
+        assert!(matches!(
+            result,
+            Err(LedError::InvalidColor {
+                channel: "g",
+                value: -1
+            })
+        ));
     }
 
     #[test]
@@ -287,14 +487,20 @@ mod tests {
         let _a = TcpListener::bind("127.0.0.1:9995").unwrap();
         let mut api = MagicHomeAPI::new("127.0.0.1", Some("9995")).unwrap();
         let result = api.set_rgb(255, 0, 300);
-        assert_eq!(result, Err("Invalid b value"));
+        assert!(matches!(
+            result,
+            Err(LedError::InvalidColor {
+                channel: "b",
+                value: 300
+            })
+        ));
     }
 
     #[test]
     fn valid_set_mode() {
         let _a = TcpListener::bind("127.0.0.1:9994").unwrap();
         let mut api = MagicHomeAPI::new("127.0.0.1", Some("9994")).unwrap();
-        let result = api.perform_action(&Actions::Chaos).unwrap();
+        let result = api.perform_action(&Actions::Chaos { speed: None }).unwrap();
         assert!(result.is_none());
     }
 
@@ -304,4 +510,62 @@ mod tests {
         let checksum = MagicHomeAPI::calc_checksum(&bytes);
         assert_eq!(checksum, 0x3d);
     }
+
+    #[test]
+    fn parse_color_hex() {
+        assert_eq!(parse_color("#ff6e00").unwrap(), (255, 110, 0));
+        assert_eq!(parse_color("ff6e00").unwrap(), (255, 110, 0));
+    }
+
+    #[test]
+    fn parse_color_triple() {
+        assert_eq!(parse_color("255, 110, 0").unwrap(), (255, 110, 0));
+    }
+
+    #[test]
+    fn parse_color_named() {
+        assert_eq!(parse_color("yellow").unwrap(), YELLOW);
+    }
+
+    #[test]
+    fn parse_color_unknown() {
+        assert!(parse_color("chartreuse").is_err());
+    }
+
+    #[test]
+    fn parse_color_invalid_triple() {
+        assert!(parse_color("255,300,0").is_err());
+    }
+
+    #[test]
+    fn status_display_plain_when_not_a_tty() {
+        // libtest's output capture doesn't redirect fd 1, so the test binary's stdout
+        // may still be a terminal; check the branch Display takes for a non-terminal
+        let status = Status {
+            power: true,
+            color: (255, 0, 0),
+            mode: "static",
+            speed: None,
+        };
+
+        assert_eq!(status.format(false), "Power: on\nColor: (255, 0, 0)\nMode: static");
+    }
+
+    #[test]
+    fn with_speed_overrides_and_inverts() {
+        let (preset, wire_speed) = with_speed(CHAOS, Some(30));
+        assert_eq!(preset, CHAOS.0);
+        assert_eq!(wire_speed, 70);
+    }
+
+    #[test]
+    fn with_speed_clamps_above_100() {
+        let (_, wire_speed) = with_speed(CHAOS, Some(150));
+        assert_eq!(wire_speed, 0);
+    }
+
+    #[test]
+    fn with_speed_defaults_when_none() {
+        assert_eq!(with_speed(RAINBOW, None), RAINBOW);
+    }
 }
@@ -2,27 +2,49 @@ use clap::Parser;
 use led::magic_home::{Actions, MagicHomeAPI};
 use std::process;
 
+mod config;
+mod server;
+
 #[derive(Parser)]
 #[clap(version, about)]
 struct Args {
-    /// Adress of controller
+    /// Adress of controller. Required unless --config is given
     #[clap(short, long)]
-    address: String,
+    address: Option<String>,
 
     /// Port to access on the controller (default: 5577)
     #[clap(short, long)]
     port: Option<String>,
 
+    /// Path to a YAML config defining multiple devices and scenes
+    #[clap(short, long)]
+    config: Option<String>,
+
+    /// Scene to apply to every device in the config. Required if --config is given
+    #[clap(short, long)]
+    scene: Option<String>,
+
     #[clap(subcommand)]
-    action: Actions,
+    action: Option<Actions>,
 }
 
 fn main() {
     let args = Args::parse();
 
-    let action = args.action;
+    if let Some(config_path) = &args.config {
+        run_scene(config_path, args.scene.as_deref());
+        return;
+    }
+
+    let address = args.address.unwrap_or_else(|| {
+        eprintln!("--address is required when not using --config");
+        process::exit(1);
+    });
 
-    let address = args.address;
+    let action = args.action.unwrap_or_else(|| {
+        eprintln!("an action is required when not using --config");
+        process::exit(1);
+    });
 
     let port = args.port.as_deref();
 
@@ -32,6 +54,14 @@ fn main() {
     });
     println!("Connection successful");
 
+    if let Actions::Serve { listen } = &action {
+        server::serve(&mut magic_api, listen).unwrap_or_else(|err| {
+            eprintln!("Server error: {}", err);
+            process::exit(1);
+        });
+        return;
+    }
+
     let status = magic_api.perform_action(&action).unwrap_or_else(|err| {
         eprintln!("Failed performing action: {}", err);
         process::exit(1);
@@ -43,3 +73,36 @@ fn main() {
 
     println!("Performed action: {:?}", action);
 }
+
+/// Loads `config_path` and applies `scene` to every device it names
+fn run_scene(config_path: &str, scene: Option<&str>) {
+    let scene = scene.unwrap_or_else(|| {
+        eprintln!("--scene is required when using --config");
+        process::exit(1);
+    });
+
+    let cfg = config::Config::from_path(config_path).unwrap_or_else(|err| {
+        eprintln!("Failed loading config: {}", err);
+        process::exit(1);
+    });
+
+    let results = config::apply_scene(&cfg, scene).unwrap_or_else(|err| {
+        eprintln!("Failed applying scene: {}", err);
+        process::exit(1);
+    });
+
+    let mut failed = false;
+    for (device, result) in results {
+        match result {
+            Ok(()) => println!("{}: ok", device),
+            Err(err) => {
+                failed = true;
+                eprintln!("{}: {}", device, err);
+            }
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,163 @@
+//! Minimal HTTP daemon that forwards requests onto a single `MagicHomeAPI` device,
+//! modeled after the bare-bones request parsing of a hobby-OS `httpd`: read the
+//! socket, split the request line into verb/path, and route by hand.
+
+use led::magic_home::{Actions, LedError, MagicHomeAPI};
+
+use std::io::prelude::*;
+use std::net::{TcpListener, TcpStream};
+
+const MAX_REQUEST_SIZE: usize = 4096;
+
+/// Listens on `listen` and forwards routed requests to `api` until the process is killed.
+/// A failed request is logged and skipped; a failed accept ends the loop with that error.
+pub fn serve(api: &mut MagicHomeAPI, listen: &str) -> Result<(), LedError> {
+    let listener = TcpListener::bind(listen)?;
+    println!("Listening on {}", listen);
+
+    for stream in listener.incoming() {
+        let stream = stream?;
+        if let Err(err) = handle_connection(stream, api) {
+            eprintln!("Error handling request: {}", err);
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_connection(mut stream: TcpStream, api: &mut MagicHomeAPI) -> Result<(), LedError> {
+    let mut buffer = [0; MAX_REQUEST_SIZE];
+    let got = stream.read(&mut buffer)?;
+    let request = String::from_utf8_lossy(&buffer[..got]);
+
+    let mut parts = request.lines().next().unwrap_or("").split_whitespace();
+    let verb = parts.next().unwrap_or("");
+    let path = parts.next().unwrap_or("");
+
+    let (status, body) = route(verb, path, api);
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+
+    stream.write_all(response.as_bytes())?;
+
+    Ok(())
+}
+
+/// Routes a verb/path pair onto the existing `Actions`/`perform_action` machinery
+fn route(verb: &str, path: &str, api: &mut MagicHomeAPI) -> (&'static str, String) {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    let action = match (verb, segments.as_slice()) {
+        ("GET", ["status"]) => Actions::Status,
+        ("POST", ["on"]) => Actions::On,
+        ("POST", ["off"]) => Actions::Off,
+        ("POST", ["color", color]) => Actions::Set {
+            color: color.to_string(),
+        },
+        ("POST", ["preset", name]) => match preset_action(name) {
+            Some(action) => action,
+            None => return ("404 Not Found", format!("unknown preset '{}'", name)),
+        },
+        _ => return ("404 Not Found", "not found".to_string()),
+    };
+
+    match api.perform_action(&action) {
+        Ok(Some(status)) => ("200 OK", status.to_plain_string()),
+        Ok(None) => ("200 OK", String::new()),
+        Err(err) => (status_for_error(&err), err.to_string()),
+    }
+}
+
+/// Picks an HTTP status code for an error from `perform_action`: a malformed color is a
+/// client mistake (400), while everything else is a device communication failure (500)
+fn status_for_error(err: &LedError) -> &'static str {
+    match err {
+        LedError::InvalidHex(_) | LedError::InvalidTriple(_) | LedError::UnknownColor(_) => {
+            "400 Bad Request"
+        }
+        _ => "500 Internal Server Error",
+    }
+}
+
+/// Maps a `{name}` path segment onto one of the preset `Actions`
+fn preset_action(name: &str) -> Option<Actions> {
+    match name {
+        "chaos" => Some(Actions::Chaos { speed: None }),
+        "rainbow" => Some(Actions::Rainbow { speed: None }),
+        "ambient" => Some(Actions::Ambient { speed: None }),
+        "red" => Some(Actions::Red),
+        "green" => Some(Actions::Green),
+        "blue" => Some(Actions::Blue),
+        "yellow" => Some(Actions::Yellow),
+        "orange" => Some(Actions::Orange),
+        "lime" => Some(Actions::Lime),
+        "purple" => Some(Actions::Purple),
+        "pink" => Some(Actions::Pink),
+        "cyan" => Some(Actions::Cyan),
+        "white" => Some(Actions::White),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    /// Binds a throwaway listener and connects a `MagicHomeAPI` to it, like the
+    /// tests in `magic_home::tests` do inline. The listener must stay alive for
+    /// as long as the API is used, or the kernel resets the accepted connection.
+    fn test_api(port: u16) -> (TcpListener, MagicHomeAPI) {
+        let listener = TcpListener::bind(("127.0.0.1", port)).unwrap();
+        let api = MagicHomeAPI::new("127.0.0.1", Some(&port.to_string())).unwrap();
+        (listener, api)
+    }
+
+    #[test]
+    fn unknown_route_is_404() {
+        let (_listener, mut api) = test_api(9980);
+        let (status, _) = route("GET", "/nonsense", &mut api);
+        assert_eq!(status, "404 Not Found");
+    }
+
+    #[test]
+    fn wrong_verb_is_404() {
+        let (_listener, mut api) = test_api(9981);
+        let (status, _) = route("POST", "/status", &mut api);
+        assert_eq!(status, "404 Not Found");
+    }
+
+    #[test]
+    fn unknown_preset_is_404() {
+        let (_listener, mut api) = test_api(9982);
+        let (status, body) = route("POST", "/preset/bogus", &mut api);
+        assert_eq!(status, "404 Not Found");
+        assert_eq!(body, "unknown preset 'bogus'");
+    }
+
+    #[test]
+    fn invalid_color_is_400() {
+        let (_listener, mut api) = test_api(9983);
+        let (status, _) = route("POST", "/color/notacolor", &mut api);
+        assert_eq!(status, "400 Bad Request");
+    }
+
+    #[test]
+    fn valid_color_is_200() {
+        let (_listener, mut api) = test_api(9984);
+        let (status, _) = route("POST", "/color/ff0000", &mut api);
+        assert_eq!(status, "200 OK");
+    }
+
+    #[test]
+    fn known_preset_is_200() {
+        let (_listener, mut api) = test_api(9985);
+        let (status, _) = route("POST", "/preset/red", &mut api);
+        assert_eq!(status, "200 OK");
+    }
+}